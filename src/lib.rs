@@ -3,12 +3,26 @@ use cursive::theme::{Color, ColorStyle, Effect};
 use cursive::vec::Vec2;
 use cursive::view::{ScrollBase, View};
 use cursive::Printer;
+use std::cell::RefCell;
 use std::collections::HashMap;
+use std::ops::Range;
 use unicode_segmentation::UnicodeSegmentation;
 use unicode_width::UnicodeWidthStr;
 
 const TAB_LEN: usize = 4;
 
+/// Syntax-colored runs covering a single line, as produced by
+/// `tokenize_line` and cached by `CodeArea::layout_cache`.
+type LineRuns = Vec<(Range<usize>, Color)>;
+
+/// Extra characters treated as word boundaries by
+/// `word_left`/`word_right`/`semantic_range`, layered on top of the
+/// built-in rule that only alphanumeric characters and `_` are word
+/// characters (so this mostly just documents common ASCII punctuation).
+/// Override with `CodeArea::word_separators` to mark additional
+/// characters as separators for non-code text.
+const DEFAULT_WORD_SEPARATORS: &str = " \t\r\n.,;:!?()[]{}<>\"'`~@#$%^&*-+=|\\/";
+
 /// Syntax for CodeArea
 ///
 /// Create your own set of symbols and words.
@@ -72,6 +86,7 @@ struct Content {
     after: String,
     selected_column: usize,
     selected_line: usize,
+    anchor: Option<usize>,
 }
 
 impl Content {
@@ -81,17 +96,73 @@ impl Content {
             after: String::new(),
             selected_column: 0,
             selected_line: 0,
+            anchor: None,
         }
     }
+    /// Display width of the cursor's column on its current line: the sum
+    /// of each grapheme's terminal width since the last newline, with
+    /// tabs expanded to `TAB_LEN` and zero-width combining marks
+    /// contributing nothing.
     fn column(&self) -> usize {
+        let line = self.before.rsplit('\n').next().unwrap_or("");
+        let tabs = line.matches('\t').count();
+        let rest_width = line.replace('\t', "").width();
+        rest_width + tabs * TAB_LEN
+    }
+    /// Number of chars making up the grapheme cluster immediately left
+    /// of the cursor (0 if `before` is empty).
+    fn prev_grapheme_len(&self) -> usize {
         self.before
-            .chars()
-            .rev()
-            .take_while(|&ch| ch != '\n')
-            .count()
+            .graphemes(true)
+            .next_back()
+            .map(|g| g.chars().count())
+            .unwrap_or(0)
+    }
+    /// Number of chars making up the grapheme cluster immediately right
+    /// of the cursor (0 if `after` is empty). `after` stores the
+    /// upcoming text reversed, so it is un-reversed before segmenting.
+    fn next_grapheme_len(&self) -> usize {
+        let upcoming: String = self.after.chars().rev().collect();
+        upcoming
+            .graphemes(true)
+            .next()
+            .map(|g| g.chars().count())
+            .unwrap_or(0)
+    }
+    /// Recompute `selected_line`/`selected_column` from `before`. Call
+    /// after any mutation of `before`/`after`.
+    fn sync_cursor(&mut self) {
+        self.selected_line = self.before.matches('\n').count();
+        self.selected_column = self.column();
+    }
+    /// Rebuild `before`/`after` from a full document string and a byte
+    /// offset marking the new split point, then derive `selected_line`
+    /// and `selected_column` from the restored `before`.
+    fn restore(&mut self, full: &str, split: usize) {
+        self.before = full[..split].to_string();
+        self.after = full[split..].chars().rev().collect();
+        self.sync_cursor();
     }
 }
 
+/// A single undoable mutation of the document, expressed as plain text
+/// inserted or removed at a byte offset into the concatenated document
+/// (see `CodeArea::get_content`).
+#[derive(Clone, Debug, PartialEq)]
+pub enum Edit {
+    Insert { at: usize, text: String },
+    Delete { at: usize, text: String },
+}
+
+/// Input mode for the optional vi-style modal layer (opt in via
+/// `CodeArea::modal`). Defaults to `Insert`, matching the historic
+/// always-insert behavior.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Mode {
+    Insert,
+    Normal,
+}
+
 /// Multi-lines code editor.
 ///
 /// CodeArea shows line numbers
@@ -102,6 +173,22 @@ pub struct CodeArea {
 
     content: Content,
 
+    undo_stack: Vec<Edit>,
+
+    redo_stack: Vec<Edit>,
+
+    coalesce_insert: bool,
+
+    word_separators: String,
+
+    clipboard: String,
+
+    layout_cache: RefCell<HashMap<String, LineRuns>>,
+
+    modal: bool,
+
+    mode: Mode,
+
     enabled: bool,
 
     scrollbase_ver: ScrollBase,
@@ -117,6 +204,14 @@ impl CodeArea {
         CodeArea {
             syntax: Syntax::new(),
             content: Content::new(),
+            undo_stack: Vec::new(),
+            redo_stack: Vec::new(),
+            coalesce_insert: false,
+            word_separators: DEFAULT_WORD_SEPARATORS.to_string(),
+            clipboard: String::new(),
+            layout_cache: RefCell::new(HashMap::new()),
+            modal: false,
+            mode: Mode::Insert,
             enabled: true,
             scrollbase_ver: ScrollBase::new().right_padding(0),
             scrollbase_hor: ScrollBase::new().right_padding(0),
@@ -131,6 +226,28 @@ impl CodeArea {
         self.syntax = syntax;
         self
     }
+    /// Mark additional characters as word boundaries for
+    /// `word_left`/`word_right`/`semantic_range`, on top of the
+    /// built-in rule that only alphanumeric characters and `_` can be
+    /// word characters; any of those listed here (e.g. `_`) becomes a
+    /// separator. Defaults to `DEFAULT_WORD_SEPARATORS`.
+    pub fn word_separators(mut self, separators: &str) -> Self {
+        self.word_separators = separators.to_string();
+        self
+    }
+    /// Opt into a vi-style modal input layer: Esc enters Normal mode
+    /// (instead of disabling the widget), where motion keys move the
+    /// cursor without inserting text and `i`/`a` return to Insert mode.
+    /// Defaults to off, preserving the always-insert behavior.
+    pub fn modal(mut self, enabled: bool) -> Self {
+        self.modal = enabled;
+        self
+    }
+    /// Current input mode, for an application to render a status
+    /// indicator. Always `Mode::Insert` unless `modal(true)` was set.
+    pub fn mode(&self) -> Mode {
+        self.mode
+    }
     pub fn enable(&mut self) {
         self.enabled = true;
     }
@@ -151,51 +268,152 @@ impl CodeArea {
 // interface
 impl CodeArea {
     pub fn insert(&mut self, ch: char) {
+        self.delete_selection();
+        let at = self.content.before.len();
         self.content.before.push(ch);
-        self.content.selected_column += 1;
+        self.content.sync_cursor();
+        self.record_insert(at, ch);
     }
     pub fn tab(&mut self) {
+        self.delete_selection();
+        let at = self.content.before.len();
         self.content.before.push('\t');
-        self.content.selected_column += TAB_LEN;
+        self.content.sync_cursor();
+        self.push_undo(Edit::Delete {
+            at,
+            text: "\t".to_string(),
+        });
     }
     pub fn new_line(&mut self) {
-        self.content.selected_column = 0;
-        self.content.selected_line += 1;
+        self.delete_selection();
+        let at = self.content.before.len();
         self.content.before.push('\n');
+        self.content.sync_cursor();
+        self.push_undo(Edit::Delete {
+            at,
+            text: "\n".to_string(),
+        });
     }
     pub fn erase(&mut self) {
         self.erase_symbol();
     }
     pub fn erase_line(&mut self) {
-        while let Some(ch) = self.erase_symbol() {
-            if ch == '\n' {
+        let mut text = String::new();
+        while let Some(piece) = self.erase_symbol_raw() {
+            let is_newline = piece == "\n";
+            text.insert_str(0, &piece);
+            if is_newline {
                 break;
             }
         }
+        if !text.is_empty() {
+            let at = self.content.before.len();
+            self.push_undo(Edit::Insert { at, text });
+        }
     }
 }
 
 // Text manage
 // Auxiliary functional
 impl CodeArea {
-    pub fn erase_symbol(&mut self) -> Option<char> {
-        let ch = self.content.before.pop();
-        match ch {
-            Some('\n') => {
-                self.content.selected_line -= 1;
-                self.content.selected_column = self
-                    .content
-                    .before
-                    .chars()
-                    .rev()
-                    .take_while(|&ch| ch != '\n')
-                    .count();
-            }
-            Some('\t') => self.content.selected_column -= TAB_LEN,
-            Some(_) => self.content.selected_column -= 1,
-            None => {}
-        };
-        ch
+    /// Erase the grapheme cluster immediately left of the cursor (e.g. a
+    /// base character together with its combining marks), returning the
+    /// removed text.
+    pub fn erase_symbol(&mut self) -> Option<String> {
+        let text = self.erase_symbol_raw();
+        if let Some(text) = &text {
+            let at = self.content.before.len();
+            self.push_undo(Edit::Insert {
+                at,
+                text: text.clone(),
+            });
+        }
+        text
+    }
+    fn erase_symbol_raw(&mut self) -> Option<String> {
+        let len = self.content.prev_grapheme_len();
+        if len == 0 {
+            return None;
+        }
+        let mut text = String::new();
+        for _ in 0..len {
+            if let Some(ch) = self.content.before.pop() {
+                text.insert(0, ch);
+            }
+        }
+        self.content.sync_cursor();
+        Some(text)
+    }
+}
+
+// Edit history
+// Undo / redo
+impl CodeArea {
+    pub fn undo(&mut self) {
+        if let Some(edit) = self.undo_stack.pop() {
+            let inverse = self.apply_edit(&edit);
+            self.redo_stack.push(inverse);
+            self.coalesce_insert = false;
+        }
+    }
+    pub fn redo(&mut self) {
+        if let Some(edit) = self.redo_stack.pop() {
+            let inverse = self.apply_edit(&edit);
+            self.undo_stack.push(inverse);
+            self.coalesce_insert = false;
+        }
+    }
+    /// Push the inverse of a just-performed edit onto the undo stack and
+    /// drop any redo history, since it no longer applies after a fresh edit.
+    fn push_undo(&mut self, edit: Edit) {
+        self.undo_stack.push(edit);
+        self.redo_stack.clear();
+        self.coalesce_insert = false;
+    }
+    /// Record a single-character insertion, coalescing it into the
+    /// previous undo entry when it is contiguous with it and no cursor
+    /// move or newline has happened in between.
+    fn record_insert(&mut self, at: usize, ch: char) {
+        self.redo_stack.clear();
+        if self.coalesce_insert {
+            if let Some(Edit::Delete { at: del_at, text }) = self.undo_stack.last_mut() {
+                if at == *del_at + text.len() {
+                    text.push(ch);
+                    self.coalesce_insert = true;
+                    return;
+                }
+            }
+        }
+        self.undo_stack.push(Edit::Delete {
+            at,
+            text: ch.to_string(),
+        });
+        self.coalesce_insert = true;
+    }
+    /// Apply an edit to the document and return its inverse, so the
+    /// caller can push it onto the opposite (undo/redo) stack.
+    fn apply_edit(&mut self, edit: &Edit) -> Edit {
+        let full = self.get_content();
+        match edit {
+            Edit::Insert { at, text } => {
+                let mut full = full;
+                full.insert_str(*at, text);
+                self.content.restore(&full, at + text.len());
+                Edit::Delete {
+                    at: *at,
+                    text: text.clone(),
+                }
+            }
+            Edit::Delete { at, text } => {
+                let mut full = full;
+                full.replace_range(*at..*at + text.len(), "");
+                self.content.restore(&full, *at);
+                Edit::Insert {
+                    at: *at,
+                    text: text.clone(),
+                }
+            }
+        }
     }
 }
 
@@ -221,23 +439,24 @@ impl CodeArea {
         while let Some(_) = self.move_right() {}
     }
     pub fn up(&mut self) {
-        let column = self.left_to_home();
+        let column = self.content.column();
+        self.left_to_home();
         self.left();
-        let upper_line_width = self.content.column();
-        if upper_line_width > column {
-            for _ in 0..(upper_line_width - column) {
-                self.move_left();
+        while self.content.column() > column {
+            if self.move_left().is_none() {
+                break;
             }
         }
     }
     pub fn down(&mut self) {
-        let mut column = self.content.column();
+        let column = self.content.column();
         self.right_to_end();
         self.right();
-        while let Some(ch) = self.move_right() {
-            column -= 1;
-            if ch == '\n' || column == 0 {
-                break;
+        while self.content.column() < column {
+            match self.move_right() {
+                Some(ref text) if text == "\n" => break,
+                Some(_) => {}
+                None => break,
             }
         }
     }
@@ -246,84 +465,311 @@ impl CodeArea {
 // Cursor manage
 // Auxiliary functional
 impl CodeArea {
-    pub fn move_right(&mut self) -> Option<char> {
-        if let Some(ch) = self.content.after.pop() {
-            self.content.before.push(ch);
-            match ch {
-                '\n' => {
-                    self.content.selected_column = 0;
-                    self.content.selected_line += 1;
-                }
-                '\t' => self.content.selected_column += TAB_LEN,
-                _ => self.content.selected_column += 1,
+    /// Move the cursor one grapheme cluster to the right (e.g. a base
+    /// character together with its combining marks), returning the text
+    /// it stepped over.
+    pub fn move_right(&mut self) -> Option<String> {
+        self.coalesce_insert = false;
+        let len = self.content.next_grapheme_len();
+        if len == 0 {
+            return None;
+        }
+        let mut text = String::new();
+        for _ in 0..len {
+            if let Some(ch) = self.content.after.pop() {
+                self.content.before.push(ch);
+                text.push(ch);
             }
-            Some(ch)
-        } else {
-            None
         }
+        self.content.sync_cursor();
+        Some(text)
     }
-    pub fn move_left(&mut self) -> Option<char> {
-        if let Some(ch) = self.content.before.pop() {
-            self.content.after.push(ch);
-            match ch {
-                '\n' => {
-                    self.content.selected_line -= 1;
-                    self.content.selected_column = self.content.column();
-                }
-                '\t' => self.content.selected_column -= TAB_LEN,
-                _ => self.content.selected_column -= 1,
+    /// Move the cursor one grapheme cluster to the left, returning the
+    /// text it stepped over.
+    pub fn move_left(&mut self) -> Option<String> {
+        self.coalesce_insert = false;
+        let len = self.content.prev_grapheme_len();
+        if len == 0 {
+            return None;
+        }
+        let mut text = String::new();
+        for _ in 0..len {
+            if let Some(ch) = self.content.before.pop() {
+                self.content.after.push(ch);
+                text.insert(0, ch);
             }
-            Some(ch)
-        } else {
-            None
         }
+        self.content.sync_cursor();
+        Some(text)
     }
     pub fn right_to_end(&mut self) -> usize {
+        self.coalesce_insert = false;
         let mut counter = 0;
-        loop {
-            match self.content.after.pop() {
-                Some('\n') => {
-                    self.content.after.push('\n');
-                    break;
-                }
-                Some('\t') => {
-                    self.content.selected_column += TAB_LEN;
-                    self.content.before.push('\t');
-                }
-                Some(ch) => {
-                    self.content.selected_column += 1;
-                    self.content.before.push(ch);
-                }
-                None => break,
+        while let Some(ch) = self.content.after.chars().last() {
+            if ch == '\n' {
+                break;
             }
+            self.move_right();
             counter += 1;
         }
         counter
     }
     pub fn left_to_home(&mut self) -> usize {
+        self.coalesce_insert = false;
         let mut counter = 0;
-        loop {
-            match self.content.before.pop() {
-                Some('\n') => {
-                    self.content.before.push('\n');
-                    break;
-                }
-                Some('\t') => {
-                    self.content.selected_column -= TAB_LEN;
-                    self.content.after.push('\t');
-                }
-                Some(ch) => {
-                    self.content.selected_column -= 1;
-                    self.content.after.push(ch);
-                }
-                None => break,
+        while let Some(ch) = self.content.before.chars().last() {
+            if ch == '\n' {
+                break;
             }
+            self.move_left();
             counter += 1;
         }
         counter
     }
 }
 
+// Cursor manage
+// Word-wise movement and selection
+impl CodeArea {
+    fn is_word_char(&self, ch: char) -> bool {
+        (ch.is_alphanumeric() || ch == '_') && !self.word_separators.contains(ch)
+    }
+    pub fn word_left(&mut self) {
+        self.skip_run_left();
+        self.skip_run_left();
+    }
+    pub fn word_right(&mut self) {
+        self.skip_run_right();
+        self.skip_run_right();
+    }
+    /// Expand outward from the cursor to the bounds (in chars) of the
+    /// enclosing run of word (or separator) characters.
+    pub fn semantic_range(&self) -> Range<usize> {
+        let cursor = self.content.before.chars().count();
+        let chars: Vec<char> = self.get_content().chars().collect();
+        if chars.is_empty() {
+            return cursor..cursor;
+        }
+        let anchor = if cursor < chars.len() {
+            cursor
+        } else {
+            cursor - 1
+        };
+        let class = self.is_word_char(chars[anchor]);
+        let mut start = anchor;
+        while start > 0 && self.is_word_char(chars[start - 1]) == class {
+            start -= 1;
+        }
+        let mut end = anchor + 1;
+        while end < chars.len() && self.is_word_char(chars[end]) == class {
+            end += 1;
+        }
+        start..end
+    }
+    fn skip_run_left(&mut self) {
+        let class = match self.content.before.chars().last() {
+            Some(ch) => self.is_word_char(ch),
+            None => return,
+        };
+        while let Some(ch) = self.content.before.chars().last() {
+            if ch == '\n' || self.is_word_char(ch) != class {
+                break;
+            }
+            self.move_left();
+        }
+    }
+    fn skip_run_right(&mut self) {
+        let class = match self.content.after.chars().last() {
+            Some(ch) => self.is_word_char(ch),
+            None => return,
+        };
+        while let Some(ch) = self.content.after.chars().last() {
+            if ch == '\n' || self.is_word_char(ch) != class {
+                break;
+            }
+            self.move_right();
+        }
+    }
+}
+
+// Selection and clipboard
+impl CodeArea {
+    fn char_offset(&self) -> usize {
+        self.content.before.chars().count()
+    }
+    /// Start a selection at the current cursor if one isn't already active.
+    /// Called by shifted movement keys before moving the cursor.
+    fn ensure_anchor(&mut self) {
+        if self.content.anchor.is_none() {
+            self.content.anchor = Some(self.char_offset());
+        }
+    }
+    /// Drop any active selection. Called by unshifted movement keys.
+    pub fn clear_selection(&mut self) {
+        self.content.anchor = None;
+    }
+    /// Char bounds `(start, end)` of the current selection, `start < end`,
+    /// or `None` if there is no selection or it is empty.
+    fn selection_bounds(&self) -> Option<(usize, usize)> {
+        let anchor = self.content.anchor?;
+        let cursor = self.char_offset();
+        if anchor == cursor {
+            return None;
+        }
+        Some(if anchor < cursor {
+            (anchor, cursor)
+        } else {
+            (cursor, anchor)
+        })
+    }
+    pub fn selected_text(&self) -> Option<String> {
+        let (start, end) = self.selection_bounds()?;
+        Some(self.get_content().chars().skip(start).take(end - start).collect())
+    }
+    /// Remove the current selection, if any, leaving the cursor at its
+    /// start. Returns whether anything was deleted.
+    pub fn delete_selection(&mut self) -> bool {
+        let (start, end) = match self.selection_bounds() {
+            Some(bounds) => bounds,
+            None => return false,
+        };
+        let full = self.get_content();
+        let byte_start = char_to_byte(&full, start);
+        let byte_end = char_to_byte(&full, end);
+        let text = full[byte_start..byte_end].to_string();
+        let mut full = full;
+        full.replace_range(byte_start..byte_end, "");
+        self.content.restore(&full, byte_start);
+        self.content.anchor = None;
+        self.push_undo(Edit::Insert {
+            at: byte_start,
+            text,
+        });
+        true
+    }
+    pub fn copy(&mut self) {
+        if let Some(text) = self.selected_text() {
+            self.clipboard = text;
+        }
+    }
+    pub fn cut(&mut self) {
+        self.copy();
+        self.delete_selection();
+    }
+    pub fn paste(&mut self) {
+        self.delete_selection();
+        for ch in self.clipboard.clone().chars() {
+            match ch {
+                '\n' => self.new_line(),
+                '\t' => self.tab(),
+                ch => self.insert(ch),
+            }
+        }
+    }
+    pub fn set_clipboard(&mut self, text: String) {
+        self.clipboard = text;
+    }
+    pub fn get_clipboard(&self) -> &str {
+        &self.clipboard
+    }
+    /// Char-offset range covered by each line of the document, in order,
+    /// for intersecting the selection against what `draw` is about to paint.
+    fn line_char_bounds(&self) -> Vec<Range<usize>> {
+        let full = self.get_content();
+        let mut bounds = Vec::new();
+        let mut start = 0;
+        for line in full.split('\n') {
+            let len = line.chars().count();
+            bounds.push(start..start + len);
+            start += len + 1;
+        }
+        bounds
+    }
+}
+
+// Search
+impl CodeArea {
+    /// Search the whole document for the next occurrence of `pattern`
+    /// after the current cursor, wrapping around to the start of the
+    /// document if nothing matches past it, and move the cursor there.
+    ///
+    /// Returns the new `(column, line)` cursor position, same shape as
+    /// `get_cursor_pos`.
+    pub fn find(&mut self, pattern: &str) -> Option<(usize, usize)> {
+        let matches = self.find_all(pattern);
+        if matches.is_empty() {
+            return None;
+        }
+        let cursor = self.content.before.len();
+        let target = matches
+            .iter()
+            .copied()
+            .find(|&at| at > cursor)
+            .unwrap_or(matches[0]);
+        let full = self.get_content();
+        self.content.restore(&full, target);
+        self.coalesce_insert = false;
+        Some(self.get_cursor_pos())
+    }
+    /// Return the byte offset of every occurrence of `pattern` in the
+    /// document, in order, for callers that want to highlight all matches.
+    pub fn find_all(&self, pattern: &str) -> Vec<usize> {
+        let full = self.get_content();
+        let pattern: Vec<char> = pattern.chars().collect();
+        if pattern.is_empty() {
+            return Vec::new();
+        }
+        let haystack: Vec<char> = full.chars().collect();
+        let byte_offsets: Vec<usize> = full.char_indices().map(|(at, _)| at).collect();
+        let failure = kmp_failure_table(&pattern);
+        kmp_search(&haystack, &pattern, &failure)
+            .into_iter()
+            .map(|char_at| byte_offsets[char_at])
+            .collect()
+    }
+}
+
+/// Build the KMP failure table for `pattern`: `f[i]` is the length of the
+/// longest proper prefix of `pattern[..=i]` that is also a suffix of it.
+fn kmp_failure_table(pattern: &[char]) -> Vec<usize> {
+    let mut f = vec![0; pattern.len()];
+    let mut len = 0;
+    let mut i = 1;
+    while i < pattern.len() {
+        if pattern[i] == pattern[len] {
+            len += 1;
+            f[i] = len;
+            i += 1;
+        } else if len > 0 {
+            len = f[len - 1];
+        } else {
+            f[i] = 0;
+            i += 1;
+        }
+    }
+    f
+}
+
+/// Scan `haystack` for every occurrence of `pattern`, returning the char
+/// index (not byte offset) each match starts at.
+fn kmp_search(haystack: &[char], pattern: &[char], failure: &[usize]) -> Vec<usize> {
+    let mut matches = Vec::new();
+    let mut matched = 0;
+    for (i, &ch) in haystack.iter().enumerate() {
+        while matched > 0 && ch != pattern[matched] {
+            matched = failure[matched - 1];
+        }
+        if ch == pattern[matched] {
+            matched += 1;
+        }
+        if matched == pattern.len() {
+            matches.push(i + 1 - pattern.len());
+            matched = failure[matched - 1];
+        }
+    }
+    matches
+}
+
 impl View for CodeArea {
     fn draw(&self, printer: &Printer) {
         printer.with_color(ColorStyle::secondary(), |printer| {
@@ -351,7 +797,20 @@ impl View for CodeArea {
                 printer.size.x - line_number_len
             };
 
-            // Background and line numbers
+            let selection = self.selection_bounds();
+            let line_bounds = if selection.is_some() {
+                self.line_char_bounds()
+            } else {
+                Vec::new()
+            };
+
+            let full = self.get_content();
+            let doc_lines: Vec<&str> = full.split('\n').collect();
+            let h_offset = self.scrollbase_hor.start_line;
+            let mut next_cache = HashMap::with_capacity(h);
+            let previous_cache = self.layout_cache.borrow();
+
+            // Background, line numbers and syntax-highlighted text
             for y in 0..h {
                 let line_number = self.scrollbase_ver.start_line + y + 1;
                 let line_number_str = format_line_number(line_number_len, line_number);
@@ -359,26 +818,54 @@ impl View for CodeArea {
                 printer.with_effect(effect, |printer| {
                     printer.print_hline((line_number_len + 2, y), w, " ");
                 });
-            }
-            /*self.scrollbase_ver.draw(printer, |printer, i| {
-                if self.rows.count() >= i {
-                    return;
+                if let Some((sel_start, sel_end)) = selection {
+                    if let Some(bounds) = line_bounds.get(line_number - 1) {
+                        let col_lo = sel_start.max(bounds.start) - bounds.start;
+                        let col_hi = sel_end.min(bounds.end) - bounds.start;
+                        let start = col_lo.max(h_offset);
+                        let end = col_hi.min(h_offset + w);
+                        if start < end {
+                            printer.with_color(ColorStyle::highlight(), |printer| {
+                                printer.print_hline(
+                                    (line_number_len + 2 + (start - h_offset), y),
+                                    end - start,
+                                    " ",
+                                );
+                            });
+                        }
+                    }
                 }
-                let row = self.rows.row(i);
-                let mut text = &self.content[row.grapheme_start..row.grapheme_end];
-                if w < text.len() {
-                    text = &self.content[row.grapheme_start..w];
+                if let Some(&line) = doc_lines.get(line_number - 1) {
+                    let runs = previous_cache
+                        .get(line)
+                        .cloned()
+                        .unwrap_or_else(|| tokenize_line(line, &self.syntax));
+                    for (range, color) in &runs {
+                        if range.end <= h_offset || range.start >= h_offset + w {
+                            continue;
+                        }
+                        let start = range.start.max(h_offset);
+                        let end = range.end.min(h_offset + w);
+                        let text: String = line.chars().skip(start).take(end - start).collect();
+                        let x = line_number_len + 2 + (start - h_offset);
+                        printer.with_color(ColorStyle::from(*color), |printer| {
+                            printer.print((x, y), &text);
+                        });
+                    }
+                    next_cache.insert(line.to_string(), runs);
                 }
-                printer.with_effect(effect, |printer| {
-                    printer.print((0, 0), text);
-                });
-            });*/
+            }
+            drop(previous_cache);
+            *self.layout_cache.borrow_mut() = next_cache;
         });
     }
     fn on_event(&mut self, event: Event) -> EventResult {
         if !self.enabled {
             return EventResult::Ignored;
         }
+        if self.modal && self.mode == Mode::Normal {
+            return self.on_event_normal(event);
+        }
         let mut consumed = true;
         match event {
             // Input
@@ -388,17 +875,87 @@ impl View for CodeArea {
             // Erase
             Event::Ctrl(Key::Backspace) => self.erase_line(),
             Event::Key(Key::Backspace) => self.erase(),
+            // History
+            Event::CtrlChar('z') => self.undo(),
+            Event::CtrlChar('y') => self.redo(),
+            // Selection and clipboard
+            Event::CtrlChar('c') => self.copy(),
+            Event::CtrlChar('x') => self.cut(),
+            Event::CtrlChar('v') => self.paste(),
+            Event::Shift(Key::Left) => {
+                self.ensure_anchor();
+                self.left();
+            }
+            Event::Shift(Key::Right) => {
+                self.ensure_anchor();
+                self.right();
+            }
+            Event::Shift(Key::Up) => {
+                self.ensure_anchor();
+                self.up();
+            }
+            Event::Shift(Key::Down) => {
+                self.ensure_anchor();
+                self.down();
+            }
+            Event::Shift(Key::Home) => {
+                self.ensure_anchor();
+                self.home();
+            }
+            Event::Shift(Key::End) => {
+                self.ensure_anchor();
+                self.end();
+            }
             // Movement
-            Event::Key(Key::Home) | Event::Ctrl(Key::Left) => self.home(),
-            Event::Key(Key::End) | Event::Ctrl(Key::Right) => self.end(),
-            Event::Ctrl(Key::Up) | Event::Ctrl(Key::Home) => self.beginning_of_file(),
-            Event::Ctrl(Key::Down) | Event::Ctrl(Key::End) => self.end_of_file(),
-            Event::Key(Key::Left) => self.left(),
-            Event::Key(Key::Right) => self.right(),
-            Event::Key(Key::Up) => self.up(),
-            Event::Key(Key::Down) => self.down(),
+            Event::Key(Key::Home) => {
+                self.clear_selection();
+                self.home();
+            }
+            Event::Key(Key::End) => {
+                self.clear_selection();
+                self.end();
+            }
+            Event::Ctrl(Key::Left) => {
+                self.clear_selection();
+                self.word_left();
+            }
+            Event::Ctrl(Key::Right) => {
+                self.clear_selection();
+                self.word_right();
+            }
+            Event::Ctrl(Key::Up) | Event::Ctrl(Key::Home) => {
+                self.clear_selection();
+                self.beginning_of_file();
+            }
+            Event::Ctrl(Key::Down) | Event::Ctrl(Key::End) => {
+                self.clear_selection();
+                self.end_of_file();
+            }
+            Event::Key(Key::Left) => {
+                self.clear_selection();
+                self.left();
+            }
+            Event::Key(Key::Right) => {
+                self.clear_selection();
+                self.right();
+            }
+            Event::Key(Key::Up) => {
+                self.clear_selection();
+                self.up();
+            }
+            Event::Key(Key::Down) => {
+                self.clear_selection();
+                self.down();
+            }
             // Stop event handling
-            Event::Key(Key::Esc) => self.disable(),
+            Event::Key(Key::Esc) => {
+                if self.modal {
+                    self.clear_selection();
+                    self.mode = Mode::Normal;
+                } else {
+                    self.disable();
+                }
+            }
             // TODO: Mouse events
             _ => consumed = false,
         }
@@ -410,6 +967,115 @@ impl View for CodeArea {
     }
 }
 
+// Modal input layer
+impl CodeArea {
+    /// Handle a key event while in `Mode::Normal`: motion keys move the
+    /// cursor without inserting text, `i`/`a` return to Insert mode, and
+    /// Esc disables the widget (mirroring non-modal Esc behavior).
+    fn on_event_normal(&mut self, event: Event) -> EventResult {
+        let mut consumed = true;
+        match event {
+            Event::Char('0') => {
+                self.clear_selection();
+                self.left_to_home();
+            }
+            Event::Char('^') => {
+                self.clear_selection();
+                self.left_to_home();
+                loop {
+                    match self.content.after.chars().last() {
+                        Some(ch) if ch != '\n' && ch.is_whitespace() => {
+                            self.move_right();
+                        }
+                        _ => break,
+                    }
+                }
+            }
+            Event::Char('$') => {
+                self.clear_selection();
+                self.right_to_end();
+            }
+            Event::Char('h') => {
+                self.clear_selection();
+                self.left();
+            }
+            Event::Char('j') => {
+                self.clear_selection();
+                self.down();
+            }
+            Event::Char('k') => {
+                self.clear_selection();
+                self.up();
+            }
+            Event::Char('l') => {
+                self.clear_selection();
+                self.right();
+            }
+            Event::Char('i') | Event::Char('a') => self.mode = Mode::Insert,
+            Event::Key(Key::Esc) => self.disable(),
+            _ => consumed = false,
+        }
+        if consumed {
+            EventResult::Consumed(None)
+        } else {
+            EventResult::Ignored
+        }
+    }
+}
+
+/// Byte offset of the `char_idx`-th character in `s` (or `s.len()` past
+/// the last character).
+fn char_to_byte(s: &str, char_idx: usize) -> usize {
+    s.char_indices()
+        .nth(char_idx)
+        .map(|(at, _)| at)
+        .unwrap_or_else(|| s.len())
+}
+
+/// Split `line` into styled runs: contiguous identifier runs colored by
+/// `syntax.words`, single symbols colored by `syntax.symbols`, and
+/// default-colored runs for everything else. Ranges are char offsets
+/// into `line`. Adjacent runs sharing a color are merged.
+fn tokenize_line(line: &str, syntax: &Syntax) -> LineRuns {
+    let chars: Vec<char> = line.chars().collect();
+    let mut runs: LineRuns = Vec::new();
+    let push_run = |runs: &mut LineRuns, range: Range<usize>, color: Color| {
+        if let Some(last) = runs.last_mut() {
+            if last.1 == color && last.0.end == range.start {
+                last.0.end = range.end;
+                return;
+            }
+        }
+        runs.push((range, color));
+    };
+    let mut i = 0;
+    while i < chars.len() {
+        let ch = chars[i];
+        if ch.is_alphanumeric() || ch == '_' {
+            let start = i;
+            while i < chars.len() && (chars[i].is_alphanumeric() || chars[i] == '_') {
+                i += 1;
+            }
+            let word: String = chars[start..i].iter().collect();
+            let color = syntax
+                .words
+                .get(&word)
+                .copied()
+                .unwrap_or(Color::TerminalDefault);
+            push_run(&mut runs, start..i, color);
+        } else {
+            let color = syntax
+                .symbols
+                .get(&ch)
+                .copied()
+                .unwrap_or(Color::TerminalDefault);
+            push_run(&mut runs, i..i + 1, color);
+            i += 1;
+        }
+    }
+    runs
+}
+
 fn format_line_number(len: usize, number: usize) -> String {
     let mut number_str = format!("{}|", number);
     for _ in 0..len - (number_str.len() - 1) {
@@ -664,4 +1330,326 @@ mod test {
         area.down();
         assert_eq!(area.get_cursor_pos(), (0,0));
     }
+    #[test]
+    fn undo_redo_insert() {
+        let mut area = CodeArea::new();
+        area.insert('a');
+        area.insert('b');
+        area.insert('c');
+        assert_eq!(&area.get_content(), "abc");
+        area.undo();
+        assert_eq!(&area.get_content(), "");
+        area.redo();
+        assert_eq!(&area.get_content(), "abc");
+    }
+    #[test]
+    fn undo_coalesces_contiguous_inserts() {
+        let mut area = CodeArea::new();
+        area.insert('a');
+        area.insert('b');
+        area.insert('c');
+        area.undo();
+        assert_eq!(&area.get_content(), "");
+        assert_eq!(area.get_cursor_pos(), (0, 0));
+    }
+    #[test]
+    fn undo_breaks_coalescing_on_cursor_move() {
+        let mut area = CodeArea::new();
+        area.insert('a');
+        area.insert('b');
+        area.left();
+        area.insert('c');
+        assert_eq!(&area.get_content(), "acb");
+        area.undo();
+        assert_eq!(&area.get_content(), "ab");
+        area.undo();
+        assert_eq!(&area.get_content(), "");
+    }
+    #[test]
+    fn undo_erase_symbol() {
+        let mut area = CodeArea::new();
+        area.insert('a');
+        area.insert('b');
+        area.erase_symbol();
+        assert_eq!(&area.get_content(), "a");
+        area.undo();
+        assert_eq!(&area.get_content(), "ab");
+        assert_eq!(area.get_cursor_pos(), (2, 0));
+    }
+    #[test]
+    fn undo_erase_line_is_one_entry() {
+        let mut area = CodeArea::new();
+        area.insert('a');
+        area.insert('b');
+        area.new_line();
+        area.insert('c');
+        area.insert('d');
+        area.erase_line();
+        assert_eq!(&area.get_content(), "ab");
+        area.undo();
+        assert_eq!(&area.get_content(), "ab\ncd");
+        assert_eq!(area.get_cursor_pos(), (2, 1));
+    }
+    #[test]
+    fn redo_cleared_by_fresh_edit() {
+        let mut area = CodeArea::new();
+        area.insert('a');
+        area.undo();
+        assert_eq!(&area.get_content(), "");
+        area.insert('b');
+        area.redo();
+        assert_eq!(&area.get_content(), "b");
+    }
+    #[test]
+    fn find_all_matches() {
+        let mut area = CodeArea::new();
+        for ch in "abcabcabc".chars() {
+            area.insert(ch);
+        }
+        assert_eq!(area.find_all("abc"), vec![0, 3, 6]);
+        assert_eq!(area.find_all("bc"), vec![1, 4, 7]);
+        assert_eq!(area.find_all("zz"), Vec::<usize>::new());
+    }
+    #[test]
+    fn find_moves_cursor_past_current_position() {
+        let mut area = CodeArea::new();
+        for ch in "foo bar foo".chars() {
+            area.insert(ch);
+        }
+        area.beginning_of_file();
+        assert_eq!(area.find("foo"), Some((8, 0)));
+        assert_eq!(area.find("foo"), Some((0, 0)));
+    }
+    #[test]
+    fn find_wraps_around() {
+        let mut area = CodeArea::new();
+        for ch in "foo bar".chars() {
+            area.insert(ch);
+        }
+        assert_eq!(area.find("foo"), Some((0, 0)));
+    }
+    #[test]
+    fn word_left_right() {
+        let mut area = CodeArea::new();
+        for ch in "foo  bar".chars() {
+            area.insert(ch);
+        }
+        area.word_left();
+        assert_eq!(area.get_cursor_pos(), (3, 0));
+        area.word_left();
+        assert_eq!(area.get_cursor_pos(), (0, 0));
+        area.word_right();
+        assert_eq!(area.get_cursor_pos(), (5, 0));
+        area.word_right();
+        assert_eq!(area.get_cursor_pos(), (8, 0));
+    }
+    #[test]
+    fn semantic_range_selects_enclosing_word() {
+        let mut area = CodeArea::new();
+        for ch in "foo bar".chars() {
+            area.insert(ch);
+        }
+        area.left();
+        area.left();
+        assert_eq!(area.semantic_range(), 4..7);
+    }
+    #[test]
+    fn semantic_range_on_empty_buffer() {
+        let area = CodeArea::new();
+        assert_eq!(area.semantic_range(), 0..0);
+    }
+    #[test]
+    fn custom_word_separators() {
+        let mut area = CodeArea::new().word_separators("_");
+        for ch in "foo_bar baz".chars() {
+            area.insert(ch);
+        }
+        area.beginning_of_file();
+        area.word_right();
+        assert_eq!(area.get_cursor_pos(), (4, 0));
+    }
+    #[test]
+    fn non_alphanumeric_symbols_are_word_boundaries_by_default() {
+        let mut area = CodeArea::new();
+        for ch in "foo\u{2022}bar".chars() {
+            area.insert(ch);
+        }
+        area.beginning_of_file();
+        area.word_right();
+        assert_eq!(area.get_cursor_pos(), (4, 0));
+    }
+    #[test]
+    fn selection_extends_with_left_and_copies() {
+        let mut area = CodeArea::new();
+        for ch in "hello".chars() {
+            area.insert(ch);
+        }
+        area.ensure_anchor();
+        area.left();
+        area.left();
+        assert_eq!(area.selected_text(), Some("lo".to_string()));
+        area.copy();
+        assert_eq!(area.get_clipboard(), "lo");
+    }
+    #[test]
+    fn cut_removes_selection_and_fills_clipboard() {
+        let mut area = CodeArea::new();
+        for ch in "hello world".chars() {
+            area.insert(ch);
+        }
+        area.ensure_anchor();
+        for _ in 0.."world".len() {
+            area.left();
+        }
+        area.cut();
+        assert_eq!(&area.get_content(), "hello ");
+        assert_eq!(area.get_clipboard(), "world");
+        assert!(area.selected_text().is_none());
+    }
+    #[test]
+    fn paste_inserts_clipboard_and_undoes() {
+        let mut area = CodeArea::new();
+        area.set_clipboard("ab\ncd".to_string());
+        area.paste();
+        assert_eq!(&area.get_content(), "ab\ncd");
+        area.undo();
+        assert_eq!(&area.get_content(), "ab\n");
+    }
+    #[test]
+    fn typing_over_selection_replaces_it() {
+        let mut area = CodeArea::new();
+        for ch in "hello".chars() {
+            area.insert(ch);
+        }
+        area.ensure_anchor();
+        for _ in 0..3 {
+            area.left();
+        }
+        area.insert('X');
+        assert_eq!(&area.get_content(), "heX");
+    }
+    #[test]
+    fn unshifted_movement_clears_selection() {
+        let mut area = CodeArea::new();
+        for ch in "hello".chars() {
+            area.insert(ch);
+        }
+        area.ensure_anchor();
+        area.left();
+        assert!(area.selected_text().is_some());
+        area.on_event(Event::Key(Key::Left));
+        assert!(area.selected_text().is_none());
+    }
+    #[test]
+    fn tokenize_line_colors_words_and_symbols() {
+        let syntax = Syntax::new()
+            .add_word("let", Color::from_256colors(1))
+            .add_symbol('=', Color::from_256colors(2));
+        let runs = tokenize_line("let x = 1", &syntax);
+        assert_eq!(runs[0], (0..3, Color::from_256colors(1)));
+        assert_eq!(runs[1], (3..6, Color::TerminalDefault));
+        assert_eq!(runs[2], (6..7, Color::from_256colors(2)));
+    }
+    #[test]
+    fn tokenize_line_merges_adjacent_default_runs() {
+        let syntax = Syntax::new();
+        let runs = tokenize_line("a.b.c", &syntax);
+        assert_eq!(runs, vec![(0..5, Color::TerminalDefault)]);
+    }
+    #[test]
+    fn wide_char_advances_column_by_two() {
+        let mut area = CodeArea::new();
+        area.insert('a');
+        area.insert('世');
+        assert_eq!(area.get_cursor_pos(), (3, 0));
+    }
+    #[test]
+    fn combining_mark_is_zero_width_and_erased_as_one_grapheme() {
+        let mut area = CodeArea::new();
+        area.insert('e');
+        area.insert('\u{301}');
+        assert_eq!(area.get_cursor_pos(), (1, 0));
+        assert_eq!(area.get_content().chars().count(), 2);
+        area.erase_symbol();
+        assert_eq!(&area.get_content(), "");
+        assert_eq!(area.get_cursor_pos(), (0, 0));
+    }
+    #[test]
+    fn move_left_steps_whole_grapheme_cluster() {
+        let mut area = CodeArea::new();
+        area.insert('a');
+        area.insert('e');
+        area.insert('\u{301}');
+        area.left();
+        assert_eq!(area.get_cursor_pos(), (1, 0));
+        area.insert('X');
+        assert_eq!(&area.get_content(), "aXe\u{301}");
+    }
+    #[test]
+    fn up_down_preserve_column_across_differing_widths() {
+        let mut area = CodeArea::new();
+        area.insert('世');
+        area.insert('界');
+        area.new_line();
+        area.insert('a');
+        area.insert('b');
+        area.up();
+        assert_eq!(area.get_cursor_pos(), (2, 0));
+    }
+    #[test]
+    fn modal_esc_enters_normal_mode_without_disabling() {
+        let mut area = CodeArea::new().modal(true);
+        area.on_event(Event::Key(Key::Esc));
+        assert_eq!(area.mode(), Mode::Normal);
+        area.on_event(Event::Char('x'));
+        assert_eq!(&area.get_content(), "");
+    }
+    #[test]
+    fn non_modal_esc_still_disables() {
+        let mut area = CodeArea::new();
+        area.on_event(Event::Key(Key::Esc));
+        assert_eq!(area.mode(), Mode::Insert);
+        area.on_event(Event::Char('x'));
+        assert_eq!(&area.get_content(), "");
+    }
+    #[test]
+    fn normal_mode_hjkl_move_without_inserting() {
+        let mut area = CodeArea::new().modal(true);
+        for ch in "ab\ncd".chars() {
+            area.insert(ch);
+        }
+        area.on_event(Event::Key(Key::Esc));
+        area.on_event(Event::Char('h'));
+        area.on_event(Event::Char('k'));
+        assert_eq!(area.get_cursor_pos(), (1, 0));
+        area.on_event(Event::Char('l'));
+        area.on_event(Event::Char('j'));
+        assert_eq!(area.get_cursor_pos(), (2, 1));
+        assert_eq!(&area.get_content(), "ab\ncd");
+    }
+    #[test]
+    fn normal_mode_zero_caret_and_dollar() {
+        let mut area = CodeArea::new().modal(true);
+        for ch in "  foo".chars() {
+            area.insert(ch);
+        }
+        area.on_event(Event::Key(Key::Esc));
+        area.on_event(Event::Char('0'));
+        assert_eq!(area.get_cursor_pos(), (0, 0));
+        area.on_event(Event::Char('^'));
+        assert_eq!(area.get_cursor_pos(), (2, 0));
+        area.on_event(Event::Char('$'));
+        assert_eq!(area.get_cursor_pos(), (5, 0));
+    }
+    #[test]
+    fn normal_mode_i_and_a_return_to_insert() {
+        let mut area = CodeArea::new().modal(true);
+        area.insert('a');
+        area.on_event(Event::Key(Key::Esc));
+        assert_eq!(area.mode(), Mode::Normal);
+        area.on_event(Event::Char('i'));
+        assert_eq!(area.mode(), Mode::Insert);
+        area.on_event(Event::Char('b'));
+        assert_eq!(&area.get_content(), "ab");
+    }
 }